@@ -0,0 +1,370 @@
+//! Streaming decoder for `Transfer-Encoding: chunked` bodies.
+//!
+//! [`Chunked`] decodes one push of bytes at a time, the same zero-copy,
+//! incremental style the header parser uses: each call to
+//! [`Chunked::parse`] returns a [`Status`] so partial input yields
+//! `Status::Partial` and malformed input yields an `Error`, never a panic.
+//!
+//! As with [`crate::Request::parse_with_state`], `buf` is the whole body
+//! seen so far, growing by appending; [`Chunked`] remembers how far it has
+//! already scanned so a `Partial` result never causes already-consumed
+//! bytes to be reprocessed (and double-counted, or re-yielded as payload)
+//! on the next call.
+
+use core::str;
+
+use crate::error::Error;
+use crate::{is_header_name_token, is_header_value_token, Header, Result, Status};
+
+/// Where a [`Chunked`] decoder currently is within the chunked-encoding
+/// grammar.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum ChunkedState {
+    /// Reading the hex digits of a chunk-size line.
+    Size,
+    /// Skipping a `;`-delimited chunk extension, up to the line ending.
+    Extension,
+    /// Consumed the `\r`, expecting the `\n` that ends the size line.
+    SizeLf,
+    /// Yielding bytes of the current chunk's payload.
+    Body,
+    /// Consumed the chunk payload, expecting the trailing `\r`.
+    BodyCr,
+    /// Consumed the chunk payload's `\r`, expecting its `\n`.
+    BodyLf,
+    /// Reading trailer header lines after the terminating `0`-size chunk.
+    Trailer,
+    /// Consumed the blank line's `\r`, expecting its `\n`.
+    EndCr,
+    /// Consumed the blank line ending the trailer section.
+    EndLf,
+    /// Decoding is finished; no further input is expected.
+    End,
+}
+
+/// A streaming decoder for `Transfer-Encoding: chunked` bodies.
+///
+/// Feed it the whole body buffer seen so far, growing by appending, the
+/// same convention [`crate::Request::parse_with_state`] uses; each call to
+/// [`parse`](Chunked::parse) returns the number of bytes of `buf` consumed
+/// in total along with any newly decoded payload bytes, which alias into
+/// `buf`.
+#[derive(Debug)]
+pub struct Chunked {
+    state: ChunkedState,
+    size: u64,
+    trailers: usize,
+    /// Offset up to which `buf` has already been scanned and folded into
+    /// `state`/`size`; a `Partial` result always leaves this pointing at
+    /// the first byte not yet accounted for.
+    pos: usize,
+}
+
+impl Chunked {
+    /// Creates a new decoder, ready to read the first chunk-size line.
+    #[inline]
+    pub fn new() -> Chunked {
+        Chunked {
+            state: ChunkedState::Size,
+            size: 0,
+            trailers: 0,
+            pos: 0,
+        }
+    }
+
+    /// Returns `true` once the terminating chunk and any trailers have
+    /// been fully consumed.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.state == ChunkedState::End
+    }
+
+    /// Decodes `buf` up to the end of the next piece of chunk payload, or
+    /// to the end of the message; call again to continue past a payload.
+    ///
+    /// On success, returns the number of bytes of `buf` consumed in total
+    /// and, if a chunk payload was available, a reference to it within
+    /// `buf`. Trailer headers, if any, are appended to `trailers`.
+    pub fn parse<'b>(
+        &mut self,
+        buf: &'b [u8],
+        trailers: &mut [Header<'b>],
+    ) -> Result<(usize, Option<&'b [u8]>)> {
+        let mut pos = self.pos;
+
+        loop {
+            match self.state {
+                ChunkedState::Size => match buf.get(pos) {
+                    // A size line needs at least one hex digit; the byte
+                    // before it is the previous line's `\n`, or nothing.
+                    Some(b';') | Some(b'\r') | Some(b'\n')
+                        if pos == 0 || !buf[pos - 1].is_ascii_hexdigit() =>
+                    {
+                        return Err(Error::Status)
+                    }
+                    Some(&b) if b.is_ascii_hexdigit() => {
+                        let digit = (b as char).to_digit(16).unwrap() as u64;
+                        self.size = match self.size.checked_mul(16).and_then(|s| s.checked_add(digit)) {
+                            Some(size) => size,
+                            None => return Err(Error::Status),
+                        };
+                        pos += 1;
+                    }
+                    Some(b';') => {
+                        self.state = ChunkedState::Extension;
+                        pos += 1;
+                    }
+                    Some(b'\r') => {
+                        pos += 1;
+                        self.state = ChunkedState::SizeLf;
+                    }
+                    // Bare LF, same as the header and trailer line endings
+                    // elsewhere in this crate accept.
+                    Some(b'\n') => {
+                        pos += 1;
+                        self.state = if self.size == 0 {
+                            ChunkedState::Trailer
+                        } else {
+                            ChunkedState::Body
+                        };
+                    }
+                    Some(_) => return Err(Error::Status),
+                    None => {
+                        self.pos = pos;
+                        return Ok(Status::Partial);
+                    }
+                },
+                ChunkedState::Extension => match buf.get(pos) {
+                    Some(b'\r') => {
+                        pos += 1;
+                        self.state = ChunkedState::SizeLf;
+                    }
+                    Some(b'\n') => {
+                        pos += 1;
+                        self.state = if self.size == 0 {
+                            ChunkedState::Trailer
+                        } else {
+                            ChunkedState::Body
+                        };
+                    }
+                    Some(_) => pos += 1,
+                    None => {
+                        self.pos = pos;
+                        return Ok(Status::Partial);
+                    }
+                },
+                ChunkedState::SizeLf => match buf.get(pos) {
+                    Some(b'\n') => {
+                        pos += 1;
+                        self.state = if self.size == 0 {
+                            ChunkedState::Trailer
+                        } else {
+                            ChunkedState::Body
+                        };
+                    }
+                    Some(_) => return Err(Error::NewLine),
+                    None => {
+                        self.pos = pos;
+                        return Ok(Status::Partial);
+                    }
+                },
+                ChunkedState::Body => {
+                    let available = (buf.len() - pos) as u64;
+                    if available == 0 {
+                        self.pos = pos;
+                        return Ok(Status::Partial);
+                    }
+                    let take = available.min(self.size) as usize;
+                    self.size -= take as u64;
+                    let chunk = &buf[pos..pos + take];
+                    pos += take;
+                    if self.size == 0 {
+                        self.state = ChunkedState::BodyCr;
+                    }
+                    self.pos = pos;
+                    return Ok(Status::Complete((pos, Some(chunk))));
+                }
+                ChunkedState::BodyCr => match buf.get(pos) {
+                    Some(b'\r') => {
+                        pos += 1;
+                        self.state = ChunkedState::BodyLf;
+                    }
+                    Some(_) => return Err(Error::NewLine),
+                    None => {
+                        self.pos = pos;
+                        return Ok(Status::Partial);
+                    }
+                },
+                ChunkedState::BodyLf => match buf.get(pos) {
+                    Some(b'\n') => {
+                        pos += 1;
+                        self.state = ChunkedState::Size;
+                    }
+                    Some(_) => return Err(Error::NewLine),
+                    None => {
+                        self.pos = pos;
+                        return Ok(Status::Partial);
+                    }
+                },
+                ChunkedState::Trailer => match buf.get(pos) {
+                    Some(b'\r') => {
+                        pos += 1;
+                        self.state = ChunkedState::EndCr;
+                    }
+                    Some(b'\n') => {
+                        pos += 1;
+                        self.state = ChunkedState::End;
+                    }
+                    Some(_) => {
+                        let (consumed, header) = match parse_trailer_line(&buf[pos..])? {
+                            Status::Complete((consumed, header)) => (consumed, header),
+                            Status::Partial => {
+                                self.pos = pos;
+                                return Ok(Status::Partial);
+                            }
+                        };
+                        pos += consumed;
+                        if let Some(header) = header {
+                            if let Some(slot) = trailers.get_mut(self.trailers) {
+                                *slot = header;
+                                self.trailers += 1;
+                            } else {
+                                return Err(Error::TooManyHeaders);
+                            }
+                        }
+                    }
+                    None => {
+                        self.pos = pos;
+                        return Ok(Status::Partial);
+                    }
+                },
+                ChunkedState::EndCr => match buf.get(pos) {
+                    Some(b'\n') => {
+                        pos += 1;
+                        self.state = ChunkedState::EndLf;
+                    }
+                    Some(_) => return Err(Error::NewLine),
+                    None => {
+                        self.pos = pos;
+                        return Ok(Status::Partial);
+                    }
+                },
+                ChunkedState::EndLf | ChunkedState::End => {
+                    self.state = ChunkedState::End;
+                    self.pos = pos;
+                    return Ok(Status::Complete((pos, None)));
+                }
+            }
+        }
+    }
+}
+
+impl Default for Chunked {
+    #[inline]
+    fn default() -> Chunked {
+        Chunked::new()
+    }
+}
+
+/// Parses a single trailer header line, returning the number of bytes
+/// consumed (including its line ending) and the parsed header.
+fn parse_trailer_line<'b>(buf: &'b [u8]) -> Result<(usize, Option<Header<'b>>)> {
+    let name_end = match buf.iter().position(|&b| b == b':') {
+        Some(i) => i,
+        None => return Ok(Status::Partial),
+    };
+    if name_end == 0 || !buf[..name_end].iter().all(|&b| is_header_name_token(b)) {
+        return Err(Error::HeaderName);
+    }
+    let name = str::from_utf8(&buf[..name_end]).map_err(|_| Error::HeaderName)?;
+
+    let mut pos = name_end + 1;
+    while buf.get(pos) == Some(&b' ') || buf.get(pos) == Some(&b'\t') {
+        pos += 1;
+    }
+
+    let value_end = match buf[pos..].iter().position(|&b| b == b'\r' || b == b'\n') {
+        Some(i) => pos + i,
+        None => return Ok(Status::Partial),
+    };
+    if !buf[pos..value_end].iter().all(|&b| is_header_value_token(b)) {
+        return Err(Error::HeaderValue);
+    }
+    let value = &buf[pos..value_end];
+
+    let consumed = match buf.get(value_end) {
+        Some(b'\r') if buf.get(value_end + 1) == Some(&b'\n') => value_end + 2,
+        Some(b'\n') => value_end + 1,
+        _ => return Ok(Status::Partial),
+    };
+
+    Ok(Status::Complete((consumed, Some(Header { name, value }))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EMPTY_HEADER;
+
+    #[test]
+    fn split_size_line_digits() {
+        let mut chunked = Chunked::new();
+        let mut trailers = [EMPTY_HEADER; 4];
+
+        assert!(chunked.parse(b"1", &mut trailers).unwrap().is_partial());
+        let (consumed, chunk) = chunked
+            .parse(b"1a\r\nabcdefghijklmnopqrstuvwxyz\r\n0\r\n\r\n", &mut trailers)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk, Some(&b"abcdefghijklmnopqrstuvwxyz"[..]));
+        assert_eq!(consumed, b"1a\r\nabcdefghijklmnopqrstuvwxyz".len());
+    }
+
+    #[test]
+    fn split_right_after_size_line() {
+        let mut chunked = Chunked::new();
+        let mut trailers = [EMPTY_HEADER; 4];
+
+        assert!(chunked.parse(b"5\r\n", &mut trailers).unwrap().is_partial());
+        let (_, chunk) = chunked
+            .parse(b"5\r\nHello\r\n0\r\n\r\n", &mut trailers)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk, Some(&b"Hello"[..]));
+    }
+
+    #[test]
+    fn bare_lf_chunk_size_line() {
+        let mut chunked = Chunked::new();
+        let mut trailers = [EMPTY_HEADER; 4];
+
+        // Matches the bare-LF line endings this crate's header parsers
+        // already accept, rather than requiring strict CRLF.
+        let (_, chunk) = chunked
+            .parse(b"5\nHello\r\n0\r\n\r\n", &mut trailers)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk, Some(&b"Hello"[..]));
+    }
+
+    #[test]
+    fn split_mid_trailers() {
+        let mut chunked = Chunked::new();
+        let mut trailers = [EMPTY_HEADER; 4];
+
+        let whole = b"0\r\nX-Foo: bar\r\nX-Baz: qux\r\n\r\n";
+
+        assert!(chunked
+            .parse(b"0\r\nX-Foo: bar\r\n", &mut trailers)
+            .unwrap()
+            .is_partial());
+        let (_, chunk) = chunked.parse(whole, &mut trailers).unwrap().unwrap();
+        assert_eq!(chunk, None);
+        assert!(chunked.is_complete());
+        assert_eq!(trailers[0].name, "X-Foo");
+        assert_eq!(trailers[0].value, b"bar");
+        assert_eq!(trailers[1].name, "X-Baz");
+        assert_eq!(trailers[1].value, b"qux");
+        assert_eq!(trailers[2], EMPTY_HEADER);
+    }
+}
@@ -0,0 +1,353 @@
+//! ICAP (Internet Content Adaptation Protocol, RFC 3507) message parsing.
+//!
+//! ICAP reuses HTTP's generic start-line and header grammar, so
+//! `IcapRequest`/`IcapResponse` mirror [`Request`](crate::Request) and lean
+//! on the same token classifiers (`is_method_token`, `is_uri_token`) and
+//! line-skipping helpers the HTTP parser uses.
+
+use core::str;
+
+use crate::error::Error;
+use crate::iter::Bytes;
+use crate::{
+    is_header_name_token, is_header_value_token, is_method_token, is_uri_token, parse_minor_version,
+    scan_token, skip_empty_lines, Header, Result, Status,
+};
+
+/// The offsets, within an ICAP message body, of the embedded HTTP sections
+/// listed by an `Encapsulated:` header.
+///
+/// Only the parts a given message actually carries are `Some`; REQMOD,
+/// RESPMOD and OPTIONS messages each use a different subset of these.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Encapsulated {
+    /// Offset of the embedded HTTP request header section (`req-hdr`).
+    pub req_hdr: Option<usize>,
+    /// Offset of the embedded HTTP response header section (`res-hdr`).
+    pub res_hdr: Option<usize>,
+    /// Offset of the embedded HTTP request body (`req-body`).
+    pub req_body: Option<usize>,
+    /// Offset of the embedded HTTP response body (`res-body`).
+    pub res_body: Option<usize>,
+    /// Offset of a body with no accompanying header section (`null-body` /
+    /// `opt-body`).
+    pub opt_body: Option<usize>,
+}
+
+/// Parses the value of an `Encapsulated:` header, e.g.
+/// `req-hdr=0, req-body=345`.
+pub fn parse_encapsulated(value: &[u8]) -> Result<Encapsulated> {
+    let value = match str::from_utf8(value) {
+        Ok(v) => v,
+        Err(_) => return Err(Error::HeaderValue),
+    };
+
+    let mut enc = Encapsulated::default();
+    for part in value.split(',') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+
+        let mut kv = part.splitn(2, '=');
+        let key = kv.next().unwrap_or("").trim();
+        let offset = match kv.next() {
+            Some(v) => match v.trim().parse::<usize>() {
+                Ok(offset) => offset,
+                Err(_) => return Err(Error::HeaderValue),
+            },
+            None => return Err(Error::HeaderValue),
+        };
+
+        match key {
+            "req-hdr" => enc.req_hdr = Some(offset),
+            "res-hdr" => enc.res_hdr = Some(offset),
+            "req-body" => enc.req_body = Some(offset),
+            "res-body" => enc.res_body = Some(offset),
+            "null-body" | "opt-body" => enc.opt_body = Some(offset),
+            _ => return Err(Error::HeaderValue),
+        }
+    }
+
+    Ok(Status::Complete(enc))
+}
+
+/// A parsed ICAP request, e.g. `REQMOD icap://icap.example.org/modify ICAP/1.0`.
+///
+/// The optional values will be `None` if a parse was not complete, and did
+/// not parse the associated property, mirroring [`Request`](crate::Request).
+#[derive(Debug, Eq, PartialEq)]
+pub struct IcapRequest<'headers, 'buf> {
+    /// The ICAP method, such as `REQMOD`, `RESPMOD` or `OPTIONS`.
+    pub method: Option<&'buf str>,
+    /// The request URI, such as `icap://icap.example.org/modify`.
+    pub uri: Option<&'buf str>,
+    /// The ICAP minor version, such as `0` for `ICAP/1.0`.
+    pub version: Option<u8>,
+    /// The parsed `Encapsulated:` header, if one was present.
+    pub encapsulated: Option<Encapsulated>,
+    /// The request headers.
+    pub headers: &'headers mut [Header<'buf>],
+}
+
+/// A parsed ICAP response, e.g. `ICAP/1.0 200 OK`.
+#[derive(Debug, Eq, PartialEq)]
+pub struct IcapResponse<'headers, 'buf> {
+    /// The ICAP minor version, such as `0` for `ICAP/1.0`.
+    pub version: Option<u8>,
+    /// The response status code, such as `200`.
+    pub code: Option<u16>,
+    /// The response reason-phrase, such as `OK`.
+    pub reason: Option<&'buf str>,
+    /// The parsed `Encapsulated:` header, if one was present.
+    pub encapsulated: Option<Encapsulated>,
+    /// The response headers.
+    pub headers: &'headers mut [Header<'buf>],
+}
+
+impl<'h, 'b> IcapRequest<'h, 'b> {
+    /// Creates a new `IcapRequest`, using a slice of headers you allocate.
+    #[inline]
+    pub fn new(headers: &'h mut [Header<'b>]) -> IcapRequest<'h, 'b> {
+        IcapRequest {
+            method: None,
+            uri: None,
+            version: None,
+            encapsulated: None,
+            headers,
+        }
+    }
+
+    /// Tries to parse a buffer of bytes into this `IcapRequest`.
+    pub fn parse(&mut self, buf: &'b [u8]) -> Result<usize> {
+        let mut bytes = Bytes::new(buf);
+        if skip_empty_lines(&mut bytes)?.is_partial() {
+            return Ok(Status::Partial);
+        }
+        let mut pos = bytes.pos();
+
+        let method_end = match scan_token(buf, pos, is_method_token) {
+            Some(end) if end > pos && buf.get(end) == Some(&b' ') => end,
+            _ => return Ok(Status::Partial),
+        };
+        self.method = Some(str::from_utf8(&buf[pos..method_end]).map_err(|_| Error::Token)?);
+        pos = method_end + 1;
+
+        let uri_end = match scan_token(buf, pos, is_uri_token) {
+            Some(end) if end > pos && buf.get(end) == Some(&b' ') => end,
+            _ => return Ok(Status::Partial),
+        };
+        self.uri = Some(str::from_utf8(&buf[pos..uri_end]).map_err(|_| Error::Token)?);
+        pos = uri_end + 1;
+
+        let (version, after_version) = match parse_icap_version(buf, pos) {
+            Some(result) => result,
+            None => return Ok(Status::Partial),
+        };
+        self.version = Some(version);
+        pos = after_version;
+
+        let (consumed, encapsulated) = match parse_icap_headers(buf, pos, self.headers)? {
+            Status::Complete(result) => result,
+            Status::Partial => return Ok(Status::Partial),
+        };
+        self.encapsulated = encapsulated;
+
+        Ok(Status::Complete(consumed))
+    }
+}
+
+impl<'h, 'b> IcapResponse<'h, 'b> {
+    /// Creates a new `IcapResponse`, using a slice of headers you allocate.
+    #[inline]
+    pub fn new(headers: &'h mut [Header<'b>]) -> IcapResponse<'h, 'b> {
+        IcapResponse {
+            version: None,
+            code: None,
+            reason: None,
+            encapsulated: None,
+            headers,
+        }
+    }
+
+    /// Tries to parse a buffer of bytes into this `IcapResponse`.
+    pub fn parse(&mut self, buf: &'b [u8]) -> Result<usize> {
+        let mut bytes = Bytes::new(buf);
+        if skip_empty_lines(&mut bytes)?.is_partial() {
+            return Ok(Status::Partial);
+        }
+        let mut pos = bytes.pos();
+
+        let (version, after_version) = match parse_icap_version(buf, pos) {
+            Some(result) => result,
+            None => return Ok(Status::Partial),
+        };
+        self.version = Some(version);
+        pos = after_version;
+        if buf.get(pos) != Some(&b' ') {
+            return Ok(Status::Partial);
+        }
+        pos += 1;
+
+        let code_end = match buf[pos..].iter().position(|&b| b == b' ' || b == b'\r' || b == b'\n') {
+            Some(i) => pos + i,
+            None => return Ok(Status::Partial),
+        };
+        if buf.get(code_end) != Some(&b' ') {
+            return Err(Error::Status);
+        }
+        let code = str::from_utf8(&buf[pos..code_end])
+            .ok()
+            .and_then(|s| s.parse::<u16>().ok())
+            .ok_or(Error::Status)?;
+        self.code = Some(code);
+        pos = code_end + 1;
+
+        let reason_end = match buf[pos..].iter().position(|&b| b == b'\r' || b == b'\n') {
+            Some(i) => pos + i,
+            None => return Ok(Status::Partial),
+        };
+        self.reason = Some(str::from_utf8(&buf[pos..reason_end]).map_err(|_| Error::Status)?);
+        pos = reason_end;
+
+        let (consumed, encapsulated) = match parse_icap_headers(buf, pos, self.headers)? {
+            Status::Complete(result) => result,
+            Status::Partial => return Ok(Status::Partial),
+        };
+        self.encapsulated = encapsulated;
+
+        Ok(Status::Complete(consumed))
+    }
+}
+
+/// Parses the `ICAP/1.x` version token starting at `pos`, returning the
+/// minor version and the offset just past it.
+fn parse_icap_version(buf: &[u8], pos: usize) -> Option<(u8, usize)> {
+    parse_minor_version(buf, pos, b"ICAP/1.")
+}
+
+/// Skips the CRLF (or LF) ending the start line, then parses header lines
+/// until the blank line terminating the header block, filling `headers`
+/// and picking out a `Encapsulated:` header if one is present.
+fn parse_icap_headers<'b>(
+    buf: &'b [u8],
+    mut pos: usize,
+    headers: &mut [Header<'b>],
+) -> Result<(usize, Option<Encapsulated>)> {
+    match buf.get(pos) {
+        Some(b'\r') if buf.get(pos + 1) == Some(&b'\n') => pos += 2,
+        Some(b'\n') => pos += 1,
+        Some(_) => return Err(Error::NewLine),
+        None => return Ok(Status::Partial),
+    }
+
+    let mut encapsulated = None;
+    let mut count = 0;
+
+    loop {
+        match buf.get(pos) {
+            Some(b'\r') if buf.get(pos + 1) == Some(&b'\n') => return Ok(Status::Complete((pos + 2, encapsulated))),
+            Some(b'\n') => return Ok(Status::Complete((pos + 1, encapsulated))),
+            Some(_) => {}
+            None => return Ok(Status::Partial),
+        }
+
+        let name_end = match scan_token(buf, pos, is_header_name_token) {
+            Some(end) if end > pos && buf.get(end) == Some(&b':') => end,
+            Some(_) => return Err(Error::HeaderName),
+            None => return Ok(Status::Partial),
+        };
+        let name = str::from_utf8(&buf[pos..name_end]).map_err(|_| Error::HeaderName)?;
+        pos = name_end + 1;
+
+        while buf.get(pos) == Some(&b' ') || buf.get(pos) == Some(&b'\t') {
+            pos += 1;
+        }
+
+        let value_end = match buf[pos..].iter().position(|&b| b == b'\r' || b == b'\n') {
+            Some(i) => pos + i,
+            None => return Ok(Status::Partial),
+        };
+        if !buf[pos..value_end].iter().all(|&b| is_header_value_token(b)) {
+            return Err(Error::HeaderValue);
+        }
+        let value = &buf[pos..value_end];
+
+        if name.eq_ignore_ascii_case("encapsulated") {
+            encapsulated = Some(match parse_encapsulated(value)? {
+                Status::Complete(enc) => enc,
+                Status::Partial => return Ok(Status::Partial),
+            });
+        }
+
+        if let Some(header) = headers.get_mut(count) {
+            *header = Header { name, value };
+            count += 1;
+        } else {
+            return Err(Error::TooManyHeaders);
+        }
+
+        pos = match buf.get(value_end) {
+            Some(b'\r') if buf.get(value_end + 1) == Some(&b'\n') => value_end + 2,
+            Some(b'\n') => value_end + 1,
+            _ => return Ok(Status::Partial),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EMPTY_HEADER;
+
+    #[test]
+    fn parses_request_with_encapsulated_header() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = IcapRequest::new(&mut headers);
+        let buf = b"REQMOD icap://icap.example.org/modify ICAP/1.0\r\n\
+                     Host: icap.example.org\r\n\
+                     Encapsulated: req-hdr=0, null-body=170\r\n\r\n";
+
+        let consumed = req.parse(buf).unwrap().unwrap();
+        assert_eq!(consumed, buf.len());
+        assert_eq!(req.method, Some("REQMOD"));
+        assert_eq!(req.uri, Some("icap://icap.example.org/modify"));
+        assert_eq!(req.version, Some(0));
+        let enc = req.encapsulated.unwrap();
+        assert_eq!(enc.req_hdr, Some(0));
+        assert_eq!(enc.opt_body, Some(170));
+    }
+
+    #[test]
+    fn parses_response_status_line() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut resp = IcapResponse::new(&mut headers);
+        let buf = b"ICAP/1.0 200 OK\r\n\r\n";
+
+        let consumed = resp.parse(buf).unwrap().unwrap();
+        assert_eq!(consumed, buf.len());
+        assert_eq!(resp.version, Some(0));
+        assert_eq!(resp.code, Some(200));
+        assert_eq!(resp.reason, Some("OK"));
+    }
+
+    #[test]
+    fn malformed_status_code_errors_without_reading_into_headers() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut resp = IcapResponse::new(&mut headers);
+
+        // A non-digit status code must fail locally, rather than the scan
+        // running past the line into the header bytes that follow.
+        assert_eq!(
+            resp.parse(b"ICAP/1.0 2xx OK\r\nHost: example.com\r\n\r\n"),
+            Err(Error::Status)
+        );
+    }
+
+    #[test]
+    fn partial_request_line_is_partial_not_error() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = IcapRequest::new(&mut headers);
+        assert!(req.parse(b"REQMOD icap://icap.example.org/modify").unwrap().is_partial());
+    }
+}
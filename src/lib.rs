@@ -1,6 +1,9 @@
 pub mod iter;
 mod error;
 #[macro_use] pub mod macros;
+pub mod icap;
+pub mod chunked;
+mod reader;
 
 use core::{fmt, mem, result, str};
 use core::mem::MaybeUninit;
@@ -198,7 +201,358 @@ impl<'h, 'b> Request<'h, 'b> {
         }
     }
 
-    fn parse(&mut self) {
+    /// Tries to parse a buffer of bytes into this `Request`.
+    pub fn parse(&mut self, buf: &'b [u8]) -> Result<usize> {
+        let mut bytes = Bytes::new(buf);
+        if skip_empty_lines(&mut bytes)?.is_partial() {
+            return Ok(Status::Partial);
+        }
+        // Offset of the request line, just past the empty lines skipped above.
+        let mut pos = buf.iter().position(|&b| b != b'\r' && b != b'\n').unwrap_or(buf.len());
+
+        let method_end = match scan_token(buf, pos, is_method_token) {
+            Some(end) if end > pos && buf.get(end) == Some(&b' ') => end,
+            _ => return Ok(Status::Partial),
+        };
+        self.method = Some(str::from_utf8(&buf[pos..method_end]).map_err(|_| Error::Token)?);
+        pos = method_end + 1;
+
+        let path_end = match scan_token(buf, pos, is_uri_token) {
+            Some(end) if end > pos && buf.get(end) == Some(&b' ') => end,
+            _ => return Ok(Status::Partial),
+        };
+        self.path = Some(str::from_utf8(&buf[pos..path_end]).map_err(|_| Error::Token)?);
+        pos = path_end + 1;
+
+        let (version, after_version) = match parse_http_version(buf, pos) {
+            Some(result) => result,
+            None => return Ok(Status::Partial),
+        };
+        self.version = Some(version);
+
+        parse_request_headers(buf, after_version, self.headers)
+    }
+
+    /// Like [`parse`](Request::parse), but its request-line scanning goes
+    /// entirely through the bounds-checked [`reader::Reader`] rather than
+    /// `unsafe { bytes.bump() }`, so this path contains no `unsafe` code
+    /// of its own (`parse` still goes through the `Bytes`-based
+    /// `skip_empty_lines`, which uses `unsafe { bytes.bump() }`).
+    /// Behaviorally identical to `parse` on every input, which is what
+    /// makes it useful for differential fuzzing the two against each other:
+    /// an empty method or URI, or a non-space byte right after either, is
+    /// treated the same way `parse` treats it, as "not enough input yet"
+    /// rather than a hard error, and exactly one space is consumed between
+    /// fields, not a run of them.
+    pub fn parse_checked(&mut self, buf: &'b [u8]) -> Result<usize> {
+        let mut reader = reader::Reader::new(buf);
+        if reader::skip_empty_lines_checked(&mut reader)?.is_partial() {
+            return Ok(Status::Partial);
+        }
+
+        reader.mark();
+        if reader::scan_token_checked(&mut reader, is_method_token)?.is_partial() {
+            return Ok(Status::Partial);
+        }
+        match reader.peek() {
+            Some(b' ') if !reader.slice_from_mark().is_empty() => {}
+            _ => return Ok(Status::Partial),
+        }
+        self.method = Some(str::from_utf8(reader.slice_from_mark()).map_err(|_| Error::Token)?);
+        reader.read_byte();
+
+        reader.mark();
+        if reader::scan_token_checked(&mut reader, is_uri_token)?.is_partial() {
+            return Ok(Status::Partial);
+        }
+        match reader.peek() {
+            Some(b' ') if !reader.slice_from_mark().is_empty() => {}
+            _ => return Ok(Status::Partial),
+        }
+        self.path = Some(str::from_utf8(reader.slice_from_mark()).map_err(|_| Error::Token)?);
+        reader.read_byte();
+
+        let pos = reader.pos();
+        let (version, after_version) = match parse_http_version(buf, pos) {
+            Some(result) => result,
+            None => return Ok(Status::Partial),
+        };
+        self.version = Some(version);
+
+        parse_request_headers(buf, after_version, self.headers)
+    }
+
+    /// Like [`parse`](Request::parse), but resumable: `state` remembers how
+    /// far a previous, partial call already scanned for the end-of-headers
+    /// terminator, so repeated calls over a buffer that only grows by
+    /// appending don't rescan bytes that can't contain it.
+    ///
+    /// If `buf` is shorter than the buffer passed to the previous call, the
+    /// saved progress is assumed stale and the scan restarts from the
+    /// beginning.
+    pub fn parse_with_state(&mut self, buf: &'b [u8], state: &mut ParseState) -> Result<usize> {
+        if buf.len() < state.last_len {
+            state.scanned_to = 0;
+        }
+        state.last_len = buf.len();
+
+        let start = state.scanned_to.saturating_sub(3);
+        match find_headers_end(&buf[start..]) {
+            Some(_) => {
+                state.scanned_to = 0;
+                state.last_len = 0;
+                self.parse(buf)
+            }
+            None => {
+                state.scanned_to = buf.len();
+                Ok(Status::Partial)
+            }
+        }
+    }
+
+    /// Returns whether the connection should be kept alive after this
+    /// request, per the parsed `version` and any `Connection:` header.
+    ///
+    /// HTTP/1.0 defaults to closing the connection unless a token
+    /// `keep-alive` is present; HTTP/1.1 defaults to keeping it alive
+    /// unless a token `close` is present. A `Connection: upgrade` is not
+    /// treated as a close, even on HTTP/1.0.
+    pub fn keep_alive(&self) -> bool {
+        if self.has_connection_token("upgrade") {
+            return true;
+        }
+        match self.version {
+            Some(0) => self.has_connection_token("keep-alive"),
+            _ => !self.has_connection_token("close"),
+        }
+    }
+
+    /// Returns whether this request is asking to switch protocols, i.e.
+    /// whether it carries a `Connection: upgrade` token.
+    pub fn is_upgrade(&self) -> bool {
+        self.has_connection_token("upgrade")
+    }
+
+    /// Determines how the request body, if any, is delimited.
+    pub fn body_encoding(&self) -> BodyEncoding {
+        if self.has_header_token("transfer-encoding", "chunked") {
+            return BodyEncoding::Chunked;
+        }
+        if let Some(value) = self.get_header("content-length") {
+            if let Ok(len) = str::from_utf8(value).unwrap_or("").parse::<u64>() {
+                return BodyEncoding::Length(len);
+            }
+        }
+        match self.version {
+            Some(0) => BodyEncoding::CloseDelimited,
+            _ => BodyEncoding::None,
+        }
+    }
+
+    /// Returns the value of the first header matching `name`,
+    /// case-insensitively.
+    ///
+    /// Use [`header_values`](Request::header_values) if `name` may appear
+    /// more than once (e.g. `Set-Cookie`) and all values are needed.
+    pub fn get_header(&self, name: &str) -> Option<&[u8]> {
+        self.header_values(name).next()
+    }
+
+    /// Returns the values of every header matching `name`,
+    /// case-insensitively, in the order they appeared.
+    ///
+    /// Useful for fields like `Set-Cookie`, `Via` or `Warning`, which are
+    /// not comma-combinable and so are expected to occur multiple times
+    /// rather than be folded into one value.
+    pub fn header_values<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'b [u8]> + 's {
+        self.headers
+            .iter()
+            .filter(move |h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value)
+    }
+
+    /// Returns whether any header matching `name` contains `token` as one
+    /// of its comma-separated, case-insensitively matched values.
+    fn has_header_token(&self, name: &str, token: &str) -> bool {
+        self.headers
+            .iter()
+            .filter(|h| h.name.eq_ignore_ascii_case(name))
+            .any(|h| header_has_token(h.value, token))
+    }
+
+    /// Returns whether the `Connection:` header contains `token`.
+    fn has_connection_token(&self, token: &str) -> bool {
+        self.has_header_token("connection", token)
+    }
+}
+
+/// Returns whether `value`, read as a comma-separated list, contains
+/// `token` case-insensitively.
+fn header_has_token(value: &[u8], token: &str) -> bool {
+    match str::from_utf8(value) {
+        Ok(value) => value
+            .split(',')
+            .any(|part| part.trim().eq_ignore_ascii_case(token)),
+        Err(_) => false,
+    }
+}
+
+/// How a parsed message's body, if any, is delimited.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BodyEncoding {
+    /// The body is encoded in chunks, per `Transfer-Encoding: chunked`.
+    Chunked,
+    /// The body is exactly this many bytes, per `Content-Length`.
+    Length(u64),
+    /// The body runs until the connection closes (HTTP/1.0 with neither a
+    /// `chunked` `Transfer-Encoding` nor a `Content-Length` that parses as
+    /// a number).
+    CloseDelimited,
+    /// There is no body.
+    None,
+}
+
+/// Saved progress from a previous, incomplete call to
+/// [`Request::parse_with_state`].
+#[derive(Clone, Debug, Default)]
+pub struct ParseState {
+    /// Length of the buffer as of the last call; used to detect a buffer
+    /// that shrank since then. Only the length is compared, so a buffer
+    /// whose earlier bytes changed without it getting shorter is not
+    /// detected.
+    last_len: usize,
+    /// Offset up to which we have already scanned without finding the
+    /// end-of-headers terminator.
+    scanned_to: usize,
+}
+
+impl ParseState {
+    /// Creates a fresh `ParseState` for a new message.
+    #[inline]
+    pub fn new() -> ParseState {
+        ParseState::default()
+    }
+}
+
+/// Finds the offset just past the first end-of-headers terminator in
+/// `buf`, if any: a bare `\n\n`, a `\r\n\r\n`, or a `\n\r\n` (the last
+/// header line ending in a bare LF, immediately followed by the blank
+/// line's CRLF), matching the line endings `parse_request_headers` itself
+/// accepts.
+fn find_headers_end(buf: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < buf.len() {
+        if buf[i] != b'\n' {
+            i += 1;
+            continue;
+        }
+        match buf.get(i + 1) {
+            Some(b'\n') => return Some(i + 2),
+            Some(b'\r') if buf.get(i + 2) == Some(&b'\n') => return Some(i + 3),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Scans a token starting at `pos`, stopping at the first byte for which
+/// `is_token` returns `false`. Returns the end offset, or `None` if the
+/// token runs off the end of the buffer without a following byte.
+///
+/// Shared with [`icap`](crate::icap), whose request/status lines use the
+/// same token grammar.
+pub(crate) fn scan_token(buf: &[u8], pos: usize, is_token: fn(u8) -> bool) -> Option<usize> {
+    let mut i = pos;
+    while i < buf.len() {
+        if !is_token(buf[i]) {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Parses a `<prefix><digit>` version token starting at `pos` (e.g.
+/// `HTTP/1.1`'s `HTTP/1.` prefix and `1` minor digit), returning the minor
+/// version and the offset just past it.
+///
+/// Shared with [`icap`](crate::icap), whose `ICAP/1.x` version line has
+/// the same shape with a different prefix.
+pub(crate) fn parse_minor_version(buf: &[u8], pos: usize, prefix: &[u8]) -> Option<(u8, usize)> {
+    if buf.len() < pos + prefix.len() + 1 {
+        return None;
+    }
+    if &buf[pos..pos + prefix.len()] != prefix {
+        return None;
+    }
+    let minor = buf[pos + prefix.len()];
+    if !minor.is_ascii_digit() {
+        return None;
+    }
+    Some((minor - b'0', pos + prefix.len() + 1))
+}
+
+/// Parses the `HTTP/1.x` version token starting at `pos`, returning the
+/// minor version and the offset just past it.
+fn parse_http_version(buf: &[u8], pos: usize) -> Option<(u8, usize)> {
+    parse_minor_version(buf, pos, b"HTTP/1.")
+}
+
+/// Skips the CRLF (or LF) ending the request line, then parses header
+/// lines until the blank line terminating the header block, filling
+/// `headers`.
+fn parse_request_headers<'b>(buf: &'b [u8], mut pos: usize, headers: &mut [Header<'b>]) -> Result<usize> {
+    match buf.get(pos) {
+        Some(b'\r') if buf.get(pos + 1) == Some(&b'\n') => pos += 2,
+        Some(b'\n') => pos += 1,
+        Some(_) => return Err(Error::NewLine),
+        None => return Ok(Status::Partial),
+    }
+
+    let mut count = 0;
+
+    loop {
+        match buf.get(pos) {
+            Some(b'\r') if buf.get(pos + 1) == Some(&b'\n') => return Ok(Status::Complete(pos + 2)),
+            Some(b'\n') => return Ok(Status::Complete(pos + 1)),
+            Some(_) => {}
+            None => return Ok(Status::Partial),
+        }
+
+        let name_end = match scan_token(buf, pos, is_header_name_token) {
+            Some(end) if end > pos && buf.get(end) == Some(&b':') => end,
+            Some(_) => return Err(Error::HeaderName),
+            None => return Ok(Status::Partial),
+        };
+        let name = str::from_utf8(&buf[pos..name_end]).map_err(|_| Error::HeaderName)?;
+        pos = name_end + 1;
+
+        while buf.get(pos) == Some(&b' ') || buf.get(pos) == Some(&b'\t') {
+            pos += 1;
+        }
+
+        let value_end = match buf[pos..].iter().position(|&b| b == b'\r' || b == b'\n') {
+            Some(i) => pos + i,
+            None => return Ok(Status::Partial),
+        };
+        if !buf[pos..value_end].iter().all(|&b| is_header_value_token(b)) {
+            return Err(Error::HeaderValue);
+        }
+        let value = &buf[pos..value_end];
+
+        if let Some(header) = headers.get_mut(count) {
+            *header = Header { name, value };
+            count += 1;
+        } else {
+            return Err(Error::TooManyHeaders);
+        }
+
+        pos = match buf.get(value_end) {
+            Some(b'\r') if buf.get(value_end + 1) == Some(&b'\n') => value_end + 2,
+            Some(b'\n') => value_end + 1,
+            _ => return Ok(Status::Partial),
+        };
     }
 }
 
@@ -244,3 +598,174 @@ fn skip_spaces(bytes: &mut Bytes<'_>) -> Result<()> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `parse` and `parse_checked` must agree on every input, since the
+    /// whole point of `parse_checked` is to be safe to differentially
+    /// fuzz against `parse`.
+    fn assert_parse_checked_matches_parse(buf: &[u8]) {
+        let mut headers_a = [EMPTY_HEADER; 4];
+        let mut headers_b = [EMPTY_HEADER; 4];
+        let mut req_a = Request::new(&mut headers_a);
+        let mut req_b = Request::new(&mut headers_b);
+
+        let result_a = req_a.parse(buf);
+        let result_b = req_b.parse_checked(buf);
+
+        match (result_a, result_b) {
+            (Ok(a), Ok(b)) => assert_eq!(a, b, "parse/parse_checked disagreed on {:?}", buf),
+            (Err(a), Err(b)) => assert_eq!(a, b, "parse/parse_checked disagreed on {:?}", buf),
+            (a, b) => panic!("parse/parse_checked disagreed on {:?}: {:?} vs {:?}", buf, a, b),
+        }
+    }
+
+    #[test]
+    fn parse_checked_matches_parse_on_complete_request() {
+        assert_parse_checked_matches_parse(b"GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n");
+    }
+
+    #[test]
+    fn parse_checked_matches_parse_on_non_space_after_method() {
+        // A tab isn't a valid method-token char, and isn't a space either;
+        // `parse` treats this as "not enough input yet", not an error.
+        assert_parse_checked_matches_parse(b"GET\tx");
+    }
+
+    #[test]
+    fn parse_checked_matches_parse_on_non_space_after_uri() {
+        assert_parse_checked_matches_parse(b"GET /a\tHTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn parse_checked_matches_parse_on_repeated_spaces() {
+        // Exactly one space is consumed between fields; a run of them
+        // leaves a stray space at the start of the next field, which is
+        // rejected the same way by both paths.
+        assert_parse_checked_matches_parse(b"GET  /a HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn parse_with_state_resumes_across_split_buffer() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        let mut state = ParseState::new();
+
+        let whole = b"GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+        assert!(req
+            .parse_with_state(b"GET /a HTTP/1.1\r\nHost: example.com\r\n", &mut state)
+            .unwrap()
+            .is_partial());
+        let consumed = req.parse_with_state(whole, &mut state).unwrap().unwrap();
+        assert_eq!(consumed, whole.len());
+        assert_eq!(req.method, Some("GET"));
+        assert_eq!(req.headers[0].name, "Host");
+    }
+
+    #[test]
+    fn parse_with_state_accepts_bare_lf_before_blank_line() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        let mut state = ParseState::new();
+
+        // The last header line ends in a bare `\n`, immediately followed by
+        // the blank line's `\r\n`; `find_headers_end` must recognize this
+        // as a complete header block, not stall on `Partial` forever.
+        let whole = b"GET /a HTTP/1.1\r\nHost: example.com\n\r\n";
+        let consumed = req.parse_with_state(whole, &mut state).unwrap().unwrap();
+        assert_eq!(consumed, whole.len());
+    }
+
+    #[test]
+    fn keep_alive_http11_defaults_to_true_unless_closed() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.1\r\n\r\n").unwrap().unwrap();
+        assert!(req.keep_alive());
+
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert!(!req.keep_alive());
+    }
+
+    #[test]
+    fn keep_alive_http10_defaults_to_false_unless_requested() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.0\r\n\r\n").unwrap().unwrap();
+        assert!(!req.keep_alive());
+
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert!(req.keep_alive());
+    }
+
+    #[test]
+    fn is_upgrade_is_not_treated_as_close_on_http10() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.0\r\nConnection: upgrade\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert!(req.is_upgrade());
+        assert!(req.keep_alive());
+    }
+
+    #[test]
+    fn body_encoding_prefers_chunked_then_content_length_then_version() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 42\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(req.body_encoding(), BodyEncoding::Chunked);
+
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.1\r\nContent-Length: 42\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(req.body_encoding(), BodyEncoding::Length(42));
+
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.0\r\n\r\n").unwrap().unwrap();
+        assert_eq!(req.body_encoding(), BodyEncoding::CloseDelimited);
+
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.1\r\n\r\n").unwrap().unwrap();
+        assert_eq!(req.body_encoding(), BodyEncoding::None);
+    }
+
+    #[test]
+    fn get_header_returns_first_case_insensitive_match() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.1\r\nHOST: example.com\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(req.get_header("host"), Some(&b"example.com"[..]));
+        assert_eq!(req.get_header("missing"), None);
+    }
+
+    #[test]
+    fn header_values_yields_every_matching_occurrence_in_order() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let mut req = Request::new(&mut headers);
+        req.parse(b"GET / HTTP/1.1\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        let values: Vec<&[u8]> = req.header_values("set-cookie").collect();
+        assert_eq!(values, vec![&b"a=1"[..], &b"b=2"[..]]);
+    }
+}
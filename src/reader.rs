@@ -0,0 +1,122 @@
+//! A bounds-checked byte cursor, for callers that want a documented
+//! guarantee that parsing never panics and never relies on unverified
+//! `unsafe` indexing, even on adversarial input.
+//!
+//! [`Reader`] backs [`Request::parse_checked`](crate::Request::parse_checked):
+//! every helper in this module is built from `peek`/`read_byte`, contains
+//! no `unsafe`. That guarantee covers `parse_checked`'s own request-line
+//! scanning; it does not extend to the rest of the crate, since `parse`'s
+//! `Bytes`-based `skip_empty_lines` (like the crate's `skip_spaces`) still
+//! uses `unsafe { bytes.bump() }`.
+//!
+//! Enabling the `forbid-unsafe-checked` feature turns that guarantee into
+//! one the compiler checks: this module is built with
+//! `#![forbid(unsafe_code)]`, so a stray `unsafe` added here in the future
+//! fails the build instead of quietly widening what `parse_checked` relies
+//! on. The rest of the crate (`parse`'s own `unsafe { bytes.bump() }`
+//! calls) is unaffected, since the attribute is scoped to this module, not
+//! the whole crate.
+#![cfg_attr(feature = "forbid-unsafe-checked", forbid(unsafe_code))]
+
+use crate::error::Error;
+use crate::{Result, Status};
+
+/// A cursor over a byte slice that only exposes bounds-checked operations.
+///
+/// Unlike [`crate::iter::Bytes`], every access here is bounds-checked
+/// (`peek`/`read_byte` return `Option`, `slice_from_mark` only slices
+/// within what has been read) instead of indexing unchecked; there is no
+/// `unsafe` anywhere in this type, so the optimizer is trusted to elide
+/// the redundant checks rather than the caller asserting they're
+/// unnecessary.
+pub struct Reader<'a> {
+    slice: &'a [u8],
+    pos: usize,
+    mark: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a new `Reader` over `slice`, positioned at the start.
+    #[inline]
+    pub fn new(slice: &'a [u8]) -> Reader<'a> {
+        Reader {
+            slice,
+            pos: 0,
+            mark: 0,
+        }
+    }
+
+    /// Reads and consumes the next byte, or `None` at the end of input.
+    #[inline]
+    pub fn read_byte(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    /// Returns the next byte without consuming it, or `None` at the end.
+    #[inline]
+    pub fn peek(&self) -> Option<u8> {
+        self.slice.get(self.pos).copied()
+    }
+
+    /// Returns the current cursor position.
+    #[inline]
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Marks the current position as the start of the next
+    /// [`slice_from_mark`](Reader::slice_from_mark).
+    #[inline]
+    pub fn mark(&mut self) {
+        self.mark = self.pos;
+    }
+
+    /// Returns the bytes consumed since the last [`mark`](Reader::mark)
+    /// call, or since the start if `mark` was never called.
+    #[inline]
+    pub fn slice_from_mark(&self) -> &'a [u8] {
+        &self.slice[self.mark.min(self.pos)..self.pos]
+    }
+}
+
+/// A checked equivalent of the crate's `skip_empty_lines`, using only
+/// bounds-checked [`Reader`] operations in place of `unsafe { bytes.bump() }`.
+pub(crate) fn skip_empty_lines_checked(reader: &mut Reader<'_>) -> Result<()> {
+    loop {
+        match reader.peek() {
+            Some(b'\r') => {
+                reader.read_byte();
+                match reader.read_byte() {
+                    Some(b'\n') => {}
+                    Some(_) => return Err(Error::NewLine),
+                    None => return Ok(Status::Partial),
+                }
+            }
+            Some(b'\n') => {
+                reader.read_byte();
+            }
+            Some(_) => {
+                reader.mark();
+                return Ok(Status::Complete(()));
+            }
+            None => return Ok(Status::Partial),
+        }
+    }
+}
+
+/// Advances past a run of bytes for which `is_token` returns `true`,
+/// stopping at the first byte that doesn't match (left for the caller to
+/// inspect via `peek`) without consuming it.
+pub(crate) fn scan_token_checked(reader: &mut Reader<'_>, is_token: fn(u8) -> bool) -> Result<()> {
+    loop {
+        match reader.peek() {
+            Some(b) if is_token(b) => {
+                reader.read_byte();
+            }
+            Some(_) => return Ok(Status::Complete(())),
+            None => return Ok(Status::Partial),
+        }
+    }
+}